@@ -3,11 +3,10 @@
 // #![allow(unreachable_code)]
 // #![allow(dead_code)]
 
-use std::{io::Write, thread, time};
+use std::{thread, time};
 
-use anyhow::bail;
 use console::{Key, Term};
-use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use serialport::{DataBits, FlowControl, Parity, StopBits};
 
 use perf_meters::*;
 
@@ -27,32 +26,31 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let mut serial = None;
-    if let Some(port) = &opts.port {
+    let mut transport: Box<dyn VuTransport> = if let Some(port) = &opts.port {
         info!("Opening serial port {}", port);
-        serial = Some(
-            serialport::new(port, BAUD_RATE)
-                .parity(Parity::None)
-                .data_bits(DataBits::Eight)
-                .stop_bits(StopBits::One)
-                .flow_control(FlowControl::None)
-                .timeout(time::Duration::new(5, 0))
-                .open()?,
-        );
-    }
+        let ser = serialport::new(port, BAUD_RATE)
+            .parity(Parity::None)
+            .data_bits(DataBits::Eight)
+            .stop_bits(StopBits::One)
+            .flow_control(FlowControl::None)
+            .timeout(time::Duration::new(5, 0))
+            .open()?;
+        Box::new(ser)
+    } else {
+        warn!("No serial port given, printing gauge frames to stdout instead");
+        Box::new(StdoutTransport)
+    };
+
+    let mut vu = Vu::new(opts.attack_ms, opts.release_ms);
 
     info!("Vu sez hi (:");
-    if let Some(ser) = &mut serial {
-        hello(&opts, ser)?;
-    }
+    hello(&mut vu, transport.as_mut())?;
 
     if opts.calibrate {
-        if let Some(ser) = &mut serial {
-            calibrate(&opts, ser)?;
-        }
+        calibrate(&mut vu, transport.as_mut())?;
     }
 
-    let mut mystats = MyStats::new();
+    let mystats = MyStats::new(&opts);
     let n_cpu = mystats.n_cpu();
 
     {
@@ -75,105 +73,32 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
+    let monitor = MonitorService::start(&opts);
+    let channel_map = build_channel_map(&opts, n_cpu)?;
+    for (i, ch) in channel_map.iter().enumerate() {
+        info!("CHAN{i}: {}", ch.source.name());
+    }
+
     let mut elapsed_ns = 0;
     let sleep_ns: u32 = (1_000_000_000.0 / opts.samplerate) as u32;
     debug!("Sleeping {} ms in each loop", sleep_ns / 1_000_000);
 
     info!("Starting measure loop");
 
-    let cpu_pwm_min = opts.cpu_pwm_min;
-    let cpu_pwm_range = opts.cpu_pwm_max - cpu_pwm_min;
-
-    let net_pwm_min = opts.net_pwm_min;
-    let net_pwm_zero = opts.net_pwm_zero;
-    let net_pwm_max = opts.net_pwm_max;
-    let net_pwm_frange = net_pwm_max - net_pwm_min;
-    let net_pwm_nrange = net_pwm_zero - net_pwm_min;
-    let net_pwm_prange = net_pwm_max - net_pwm_zero;
-
-    let mem_pwm_min = opts.mem_pwm_min;
-    let mem_pwm_range = opts.mem_pwm_max - mem_pwm_min;
-
     loop {
         thread::sleep(time::Duration::new(0, sleep_ns - elapsed_ns));
         let start = time::Instant::now();
 
         debug!("Last elapsed: {} µs", elapsed_ns / 1000);
-        mystats.refresh();
-
-
-        // CHAN0 - CPU stats + gauge, rates are sorted largest first
-        let cpu_rates = mystats.cpu_usage();
-        let mut cpu_gauge = if n_cpu >= 2 {
-            (cpu_rates[0] + cpu_rates[1]) / 2.0
-        } else {
-            cpu_rates[0]
-        };
-
-        if n_cpu >= 6 {
-            cpu_gauge += (cpu_rates[2] + cpu_rates[3]) / 2.0;
-            cpu_gauge += (cpu_rates[4] + cpu_rates[5]) / 3.0;
-        } else if n_cpu >= 4 {
-            cpu_gauge += (cpu_rates[2] + cpu_rates[3]) * 0.80;
-        } else {
-            cpu_gauge *= 2.56;
-        }
-        // deliberately print out cpu gauge without clamping yet
-        cpu_gauge = cpu_gauge.clamp(0.0, 255.0);
-        let cpu_pwm = (cpu_pwm_min + (cpu_gauge * cpu_pwm_range / 256.0)).clamp(0.0, 255.0);
-        debug!(
-            "CPU gauge: {cpu_gauge:.1}, pwm: {cpu_pwm:.0} -- {list}",
-            list = cpu_rates
-                .iter()
-                .take(4)
-                .map(|a| format!("{a:.1}"))
-                .collect::<Vec<String>>()
-                .join(" ")
-                .as_str()
-        );
-
-
-        // CHAN1 - NET stats + gauge
-        let mut net_rate = mystats.net_bits();
-        if opts.net_gauge_abs {
-            net_rate = net_rate.abs();
-        }
-        let mut net_gauge = 256.0 * (((net_rate as f32) / 1_000_000.0) / opts.net_gauge_mbps);
-        net_gauge = net_gauge.clamp(-255.0, 255.0);
-        let net_pwm = if opts.net_gauge_abs {
-            net_pwm_min + (net_gauge * net_pwm_frange / 256.0)
-        } else {
-            let range = if net_gauge < 0.0 {
-                net_pwm_nrange
-            } else {
-                net_pwm_prange
-            };
-            net_pwm_zero + net_gauge * range / 256.0
-        }
-            .clamp(0.0, 255.0);
-        debug!(
-            "NET rate: {rate} kbps, gauge: {net_gauge:.0}, pwm: {net_pwm:.0}",
-            rate = net_rate / 1000,
-        );
-
-
-        // CHAN2 - disk IO
-        let disk_io = mystats.disk_io();
-        let dsk_pwm = 256.0 * ((disk_io as f32) / 102_400.0).clamp(0.0, 255.0);
-        debug!("DSK pwm: {dsk_pwm:.0}");
-
-        // CHAN3 - MEM stats + gauge
-        let mem_pct = mystats.mem_usage();
-        let mut mem_gauge = 2.56 * mem_pct;
-        mem_gauge = mem_gauge.clamp(0.0, 255.0);
-        let mem_pwm = (mem_pwm_min + (mem_gauge * mem_pwm_range / 256.0)).clamp(0.0, 255.0);
-        debug!("MEM used: {mem_pct:.1}%, gauge: {mem_gauge:.0}, pwm: {mem_pwm:.0}");
-
-        if let Some(ser) = &mut serial {
-            set_vu(&opts, ser, 0, cpu_pwm as i16)?;
-            set_vu(&opts, ser, 1, net_pwm as i16)?;
-            set_vu(&opts, ser, 2, dsk_pwm as i16)?;
-            set_vu(&opts, ser, 3, mem_pwm as i16)?;
+        let snap = monitor.snapshot();
+
+        // the loop keeps a steady cadence of ~1/samplerate seconds between
+        // gauge updates, which is the dt the ballistics smoothing wants
+        let dt = time::Duration::new(0, sleep_ns);
+        for (i, ch) in channel_map.iter().enumerate() {
+            let pwm = ch.pwm(&snap);
+            debug!("CHAN{i} ({}) pwm: {pwm:.0}", ch.source.name());
+            vu.set(transport.as_mut(), Channel::from_index(i as u8), pwm as i16, dt)?;
         }
         // keep the sample rate from drifting
         elapsed_ns = start.elapsed().as_nanos() as u32;
@@ -181,27 +106,29 @@ fn main() -> anyhow::Result<()> {
 }
 
 
-fn hello(opts: &OptsCommon, ser: &mut Box<dyn SerialPort>) -> anyhow::Result<()> {
+fn hello(vu: &mut Vu, transport: &mut dyn VuTransport) -> anyhow::Result<()> {
+    let dt = time::Duration::new(0, 3_000_000);
     for i in (0i16..=255)
         .chain((128..=255).rev())
         .chain(128..=255)
         .chain((0..=255).rev())
     {
         for c in 0u8..=3 {
-            set_vu(opts, ser, c, i)?;
+            vu.set(transport, Channel::from_index(c), i, dt)?;
         }
-        thread::sleep(time::Duration::new(0, 3_000_000));
+        thread::sleep(dt);
     }
     Ok(())
 }
 
 
-fn calibrate(opts: &OptsCommon, ser: &mut Box<dyn SerialPort>) -> anyhow::Result<()> {
+fn calibrate(vu: &mut Vu, transport: &mut dyn VuTransport) -> anyhow::Result<()> {
     let mut chan: usize = 0;
     let mut gauges = [1i16; 4];
     warn!("Entering calibration mode.\r\nUse arrow keys left/right to change channel.\r\nUse up/down to move gauge.");
     warn!("Press Esc to quit.");
     let term = Term::stdout();
+    let mut last_ts = time::Instant::now();
     loop {
         eprint!(
             "\rChan: {} gauges: [1]={:03} [2]={:03} [3]={:03} [4]={:03}",
@@ -211,7 +138,14 @@ fn calibrate(opts: &OptsCommon, ser: &mut Box<dyn SerialPort>) -> anyhow::Result
             gauges[2],
             gauges[3]
         );
-        set_vu(opts, ser, (chan + 1) as u8, gauges[chan])?;
+        let now = time::Instant::now();
+        vu.set(
+            transport,
+            Channel::from_index((chan + 1) as u8),
+            gauges[chan],
+            now.duration_since(last_ts),
+        )?;
+        last_ts = now;
 
         let k = term.read_key()?;
         match k {
@@ -240,40 +174,4 @@ fn calibrate(opts: &OptsCommon, ser: &mut Box<dyn SerialPort>) -> anyhow::Result
         gauges[chan] = gauges[chan].clamp(0, 255);
     }
 }
-
-
-const CHANNELS_NUM: usize = 192; // Remember: channel cmd byte has offset 0x30
-
-fn set_vu(
-    opts: &OptsCommon,
-    ser: &mut Box<dyn SerialPort>,
-    channel: u8,
-    mut pwm: i16,
-) -> anyhow::Result<()> {
-    static mut LAST_VAL: [i16; CHANNELS_NUM] = [0; CHANNELS_NUM];
-
-    let ch_i = channel as usize;
-    if ch_i >= CHANNELS_NUM {
-        bail!(
-            "Channel number too large: {ch_i} (maximum {}",
-            CHANNELS_NUM - 1
-        );
-    }
-
-    // limit to gauge values between 0..255
-    pwm = pwm.clamp(0, 255);
-
-    // do some smoothing -- only move the gauge MAX_DELTA at once
-    let delta = unsafe { pwm - LAST_VAL[ch_i] };
-    let delta_sig = delta.signum();
-    let delta_trunc = delta.abs().min(opts.pwm_max_delta);
-    let new_value = unsafe { LAST_VAL[ch_i] + delta_sig * delta_trunc };
-    unsafe {
-        LAST_VAL[ch_i] = new_value;
-    }
-    let cmd_value = new_value.clamp(0, 255) as u8;
-
-    let cmd_buf: [u8; 4] = [0xFD, 0x02, 0x30 + channel, cmd_value];
-    Ok(ser.write_all(&cmd_buf)?)
-}
 // EOF
@@ -21,28 +21,93 @@ pub struct OptsCommon {
     #[arg(short, long, default_value_t = 5.0)]
     pub samplerate: f32,
 
-    #[arg(long, default_value_t = 32)]
-    pub pwm_max_delta: i16,
+    // VU ballistics: independent attack/release time constants for the
+    // exponential smoothing applied to every gauge move
+    #[arg(long, default_value_t = 60)]
+    pub attack_ms: u64,
+    #[arg(long, default_value_t = 300)]
+    pub release_ms: u64,
+
+    // metric-to-channel routing -- any of cpu, net, net_errors, disk_util,
+    // disk, disk_read, disk_write, mem, loadavg can be assigned to any of
+    // the 4 channels
+    #[arg(long, default_value = "cpu")]
+    pub ch0: String,
+    #[arg(long, default_value = "net")]
+    pub ch1: String,
+    #[arg(long, default_value = "disk_util")]
+    pub ch2: String,
+    #[arg(long, default_value = "mem")]
+    pub ch3: String,
+
+    // per-channel PWM scaling: pwm_min..pwm_max for unidirectional sources;
+    // for the bidirectional net-throughput source (without --net-gauge-abs)
+    // pwm_zero is the output at gauge value 0, letting it swing either side
+    #[arg(long, default_value_t = 0.0)]
+    pub ch0_pwm_min: f32,
     #[arg(long, default_value_t = 0.0)]
-    pub cpu_pwm_min: f32,
+    pub ch0_pwm_zero: f32,
     #[arg(long, default_value_t = 255.0)]
-    pub cpu_pwm_max: f32,
+    pub ch0_pwm_max: f32,
 
-    #[arg(long)]
-    pub net_gauge_abs: bool,
-    #[arg(long, default_value_t = 100.0)]
-    pub net_gauge_mbps: f32,
     #[arg(long, default_value_t = 0.0)]
-    pub net_pwm_min: f32,
+    pub ch1_pwm_min: f32,
     #[arg(long, default_value_t = 128.0)]
-    pub net_pwm_zero: f32,
+    pub ch1_pwm_zero: f32,
+    #[arg(long, default_value_t = 255.0)]
+    pub ch1_pwm_max: f32,
+
+    #[arg(long, default_value_t = 0.0)]
+    pub ch2_pwm_min: f32,
+    #[arg(long, default_value_t = 0.0)]
+    pub ch2_pwm_zero: f32,
     #[arg(long, default_value_t = 255.0)]
-    pub net_pwm_max: f32,
+    pub ch2_pwm_max: f32,
 
     #[arg(long, default_value_t = 0.0)]
-    pub mem_pwm_min: f32,
+    pub ch3_pwm_min: f32,
+    #[arg(long, default_value_t = 0.0)]
+    pub ch3_pwm_zero: f32,
     #[arg(long, default_value_t = 255.0)]
-    pub mem_pwm_max: f32,
+    pub ch3_pwm_max: f32,
+
+    #[arg(long)]
+    pub net_gauge_abs: bool,
+    #[arg(long, default_value_t = 100.0)]
+    pub net_gauge_mbps: f32,
+    // errors+drops per second that maps to full scale on a net_errors channel
+    #[arg(long, default_value_t = 10.0)]
+    pub net_errors_max_eps: f32,
+    // sectors/sec that maps to full scale on a disk_read/disk_write channel
+    #[arg(long, default_value_t = 102_400.0)]
+    pub disk_sectors_max: f32,
+
+    // MonitorService per-metric sampling cadence, in ms -- slow-changing
+    // stats (mem, disk) needn't be re-read from /proc as often as the
+    // meter animation updates
+    #[arg(long, default_value_t = 300)]
+    pub monitor_tick_ms: u64,
+    #[arg(long, default_value_t = 500)]
+    pub cpu_interval_ms: u64,
+    #[arg(long, default_value_t = 1000)]
+    pub net_interval_ms: u64,
+    #[arg(long, default_value_t = 2000)]
+    pub disk_interval_ms: u64,
+    #[arg(long, default_value_t = 2000)]
+    pub mem_interval_ms: u64,
+
+    // explicit disk device allowlist, e.g. --disk-device sda --disk-device
+    // nvme0n1 -- when empty, whole disks are auto-detected and partitions
+    // are excluded
+    #[arg(long)]
+    pub disk_device: Vec<String>,
+
+    // interface filters applied to both net_bits() and net_errors(); an
+    // empty include list means "all interfaces except excluded ones"
+    #[arg(long)]
+    pub net_iface_include: Vec<String>,
+    #[arg(long, default_values_t = [String::from("lo")])]
+    pub net_iface_exclude: Vec<String>,
 }
 
 impl OptsCommon {
@@ -7,10 +7,16 @@ pub use serialport::SerialPort;
 pub use tracing::*;
 
 pub use config::*;
+pub use monitor::*;
+pub use routing::*;
 pub use stats::*;
+pub use transport::*;
 
 mod config;
+mod monitor;
+mod routing;
 mod stats;
+mod transport;
 
 pub const N_CHANS: usize = 4;
 
@@ -39,36 +45,113 @@ impl Channel {
             Channel::Ch3 => Channel::Ch2,
         }
     }
+
+    // indices beyond Ch3 clamp to Ch3 rather than panicking, since there
+    // are only N_CHANS physical gauges
+    pub fn from_index(i: u8) -> Channel {
+        match i {
+            0 => Channel::Ch0,
+            1 => Channel::Ch1,
+            2 => Channel::Ch2,
+            _ => Channel::Ch3,
+        }
+    }
 }
 
 pub struct Vu {
-    last_val: [i16; N_CHANS],
-    max_delta: i16,
+    last_val: [f32; N_CHANS],
+    tau_attack: std::time::Duration,
+    tau_release: std::time::Duration,
 }
 impl Vu {
-    pub fn new(max_delta: i16) -> Self {
+    pub fn new(attack_ms: u64, release_ms: u64) -> Self {
         Self {
-            last_val: [0; _],
-            max_delta,
+            last_val: [0.0; _],
+            tau_attack: std::time::Duration::from_millis(attack_ms),
+            tau_release: std::time::Duration::from_millis(release_ms),
         }
     }
 
-    pub fn set(&mut self, ser: &mut Box<dyn SerialPort>, channel: Channel, pwm: i16) -> anyhow::Result<()> {
+    // move the gauge towards `pwm` by exponential smoothing with a
+    // different time constant depending on whether it's rising (attack)
+    // or falling (release), mimicking real VU meter ballistics
+    pub fn set(
+        &mut self,
+        transport: &mut dyn VuTransport,
+        channel: Channel,
+        pwm: i16,
+        dt: std::time::Duration,
+    ) -> anyhow::Result<()> {
         let ch_i = channel as usize;
 
         // limit to gauge values between 0..255
-        let pwm = pwm.clamp(0, 255);
+        let target = pwm.clamp(0, 255) as f32;
+        let last = self.last_val[ch_i];
 
-        // do some smoothing -- only move the gauge MAX_DELTA at once
-        let delta = pwm - self.last_val[ch_i];
-        let delta_sig = delta.signum();
-        let delta_trunc = delta.abs().min(self.max_delta);
-        let new_value = self.last_val[ch_i] + delta_sig * delta_trunc;
+        let tau = if target >= last {
+            self.tau_attack
+        } else {
+            self.tau_release
+        };
+        let alpha = 1.0 - (-dt.as_secs_f32() / tau.as_secs_f32()).exp();
+        let new_value = last + alpha * (target - last);
         self.last_val[ch_i] = new_value;
 
-        let cmd_value = new_value.clamp(0, 255) as u8;
-        let cmd_buf: [u8; 4] = [0xFD, 0x02, 0x30 + channel as u8, cmd_value];
-        Ok(ser.write_all(&cmd_buf)?)
+        let cmd_value = new_value.round().clamp(0.0, 255.0) as u8;
+        transport.send_frame(channel as u8, cmd_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vu_attack_moves_towards_target_and_emits_a_frame() {
+        let mut vu = Vu::new(60, 300);
+        let mut transport = RecordingTransport::default();
+
+        vu.set(&mut transport, Channel::Ch0, 255, std::time::Duration::from_millis(60))
+            .unwrap();
+
+        assert_eq!(transport.frames.len(), 1);
+        let frame = transport.frames[0];
+        assert_eq!(frame, [0xFD, 0x02, 0x30, frame[3]]);
+        // one attack time-constant should move it roughly 1-1/e of the way
+        assert!((100..200).contains(&(frame[3] as i32)));
+    }
+
+    #[test]
+    fn vu_release_is_slower_than_attack() {
+        let mut vu = Vu::new(60, 300);
+        let mut transport = RecordingTransport::default();
+
+        vu.set(&mut transport, Channel::Ch1, 255, std::time::Duration::from_millis(60))
+            .unwrap();
+        let risen = transport.frames[0][3];
+
+        vu.set(&mut transport, Channel::Ch1, 0, std::time::Duration::from_millis(60))
+            .unwrap();
+        let fallen = transport.frames[1][3];
+
+        // release's longer time constant means less movement in the same dt
+        let attack_delta = risen as i32;
+        let release_delta = risen as i32 - fallen as i32;
+        assert!(release_delta < attack_delta);
+    }
+
+    #[test]
+    fn vu_clamps_pwm_targets_to_0_255() {
+        let mut vu = Vu::new(10, 10);
+        let mut transport = RecordingTransport::default();
+
+        vu.set(&mut transport, Channel::Ch2, 1000, std::time::Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(transport.frames[0][3], 255);
+
+        vu.set(&mut transport, Channel::Ch2, -50, std::time::Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(transport.frames[1][3], 0);
     }
 }
 // EOF
@@ -0,0 +1,154 @@
+// monitor.rs
+
+use std::sync::{Arc, Mutex};
+use std::{thread, time};
+
+use crate::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MonitorIntervals {
+    pub tick: time::Duration,
+    pub cpu: time::Duration,
+    pub net: time::Duration,
+    pub disk: time::Duration,
+    pub mem: time::Duration,
+}
+
+impl MonitorIntervals {
+    pub fn from_opts(opts: &OptsCommon) -> Self {
+        Self {
+            tick: time::Duration::from_millis(opts.monitor_tick_ms),
+            cpu: time::Duration::from_millis(opts.cpu_interval_ms),
+            net: time::Duration::from_millis(opts.net_interval_ms),
+            disk: time::Duration::from_millis(opts.disk_interval_ms),
+            mem: time::Duration::from_millis(opts.mem_interval_ms),
+        }
+    }
+}
+
+// latest published values -- read by the (faster) serial-writing loop,
+// written by the MonitorService background thread
+#[derive(Debug, Default, Clone)]
+pub struct StatsSnapshot {
+    pub cpu_rates: Vec<f32>,
+    pub net_bits: i64,
+    pub net_errors: f64,
+    pub disk_io: f64,
+    pub disk_read: f64,
+    pub disk_write: f64,
+    pub disk_util: f64,
+    pub mem_pct: f32,
+    pub loadavg: f32,
+}
+
+// Samples MyStats on a background thread, each metric on its own interval,
+// and publishes the freshest values behind a shared snapshot. This keeps
+// the meter-writing loop decoupled from slow /proc reads (memory, disk)
+// so it can run at its own, higher cadence.
+pub struct MonitorService {
+    snapshot: Arc<Mutex<StatsSnapshot>>,
+    _worker: thread::JoinHandle<()>,
+}
+
+impl MonitorService {
+    pub fn start(opts: &OptsCommon) -> Self {
+        let intervals = MonitorIntervals::from_opts(opts);
+        let mut mystats = MyStats::new(opts);
+        mystats.refresh();
+
+        let snapshot = Arc::new(Mutex::new(StatsSnapshot {
+            cpu_rates: mystats.cpu_usage(),
+            net_bits: mystats.net_bits(),
+            net_errors: mystats.net_errors(),
+            disk_io: mystats.disk_io(),
+            disk_read: mystats.disk_read_io(),
+            disk_write: mystats.disk_write_io(),
+            disk_util: mystats.disk_util(),
+            mem_pct: mystats.mem_usage(),
+            loadavg: mystats.load_avg(),
+        }));
+        let worker_snapshot = Arc::clone(&snapshot);
+
+        let worker = thread::spawn(move || {
+            let now = time::Instant::now();
+            let mut last_cpu = now;
+            let mut last_net = now;
+            let mut last_disk = now;
+            let mut last_mem = now;
+
+            loop {
+                thread::sleep(intervals.tick);
+                let now = time::Instant::now();
+
+                let cpu_rates = if now.duration_since(last_cpu) >= intervals.cpu {
+                    mystats.refresh_cpu();
+                    last_cpu = now;
+                    Some(mystats.cpu_usage())
+                } else {
+                    None
+                };
+                let net_bits = if now.duration_since(last_net) >= intervals.net {
+                    mystats.refresh_net();
+                    last_net = now;
+                    Some((mystats.net_bits(), mystats.net_errors()))
+                } else {
+                    None
+                };
+                let disk_io = if now.duration_since(last_disk) >= intervals.disk {
+                    if let Err(e) = mystats.refresh_disk() {
+                        error!("Error refreshing diskstats: {e} (ignored)");
+                    }
+                    last_disk = now;
+                    Some((
+                        mystats.disk_io(),
+                        mystats.disk_read_io(),
+                        mystats.disk_write_io(),
+                        mystats.disk_util(),
+                    ))
+                } else {
+                    None
+                };
+                let mem_pct = if now.duration_since(last_mem) >= intervals.mem {
+                    mystats.refresh_mem();
+                    last_mem = now;
+                    Some((mystats.mem_usage(), mystats.load_avg()))
+                } else {
+                    None
+                };
+
+                if cpu_rates.is_none() && net_bits.is_none() && disk_io.is_none() && mem_pct.is_none() {
+                    continue;
+                }
+
+                let mut snap = worker_snapshot.lock().unwrap();
+                if let Some(r) = cpu_rates {
+                    snap.cpu_rates = r;
+                }
+                if let Some((b, e)) = net_bits {
+                    snap.net_bits = b;
+                    snap.net_errors = e;
+                }
+                if let Some((d, r, w, u)) = disk_io {
+                    snap.disk_io = d;
+                    snap.disk_read = r;
+                    snap.disk_write = w;
+                    snap.disk_util = u;
+                }
+                if let Some((m, l)) = mem_pct {
+                    snap.mem_pct = m;
+                    snap.loadavg = l;
+                }
+            }
+        });
+
+        Self {
+            snapshot,
+            _worker: worker,
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+}
+// EOF
@@ -0,0 +1,397 @@
+// routing.rs
+
+use anyhow::bail;
+
+use crate::*;
+
+// A metric source yields a gauge value from the latest snapshot. Most
+// sources are normalized to 0..255; NetThroughput is the one exception,
+// clamped to -255..255 around zero. net_bits() sums rx and tx, so its
+// sample never actually goes below zero.
+pub trait MetricSource {
+    fn sample(&self, snap: &StatsSnapshot) -> f32;
+    fn name(&self) -> &'static str;
+
+    // true if sample() can return negative values that should pivot around
+    // pwm_zero; false (the default) for sources that only ever go 0..255,
+    // which scale straight across pwm_min..pwm_max instead
+    fn is_bidirectional(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct CpuUsage {
+    pub n_cpu: usize,
+}
+impl MetricSource for CpuUsage {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        let r = &snap.cpu_rates;
+        if r.is_empty() {
+            return 0.0;
+        }
+        let mut gauge = if self.n_cpu >= 2 && r.len() >= 2 {
+            (r[0] + r[1]) / 2.0
+        } else {
+            r[0]
+        };
+        if self.n_cpu >= 6 && r.len() >= 6 {
+            gauge += (r[2] + r[3]) / 2.0;
+            gauge += (r[4] + r[5]) / 3.0;
+        } else if self.n_cpu >= 4 && r.len() >= 4 {
+            gauge += (r[2] + r[3]) * 0.80;
+        } else {
+            gauge *= 2.56;
+        }
+        gauge.clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+}
+
+#[derive(Debug)]
+pub struct NetThroughput {
+    pub gauge_abs: bool,
+    pub gauge_mbps: f32,
+}
+impl MetricSource for NetThroughput {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        let mut rate = snap.net_bits;
+        if self.gauge_abs {
+            rate = rate.abs();
+        }
+        (256.0 * ((rate as f32) / 1_000_000.0) / self.gauge_mbps).clamp(-255.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "net"
+    }
+
+    fn is_bidirectional(&self) -> bool {
+        !self.gauge_abs
+    }
+}
+
+#[derive(Debug)]
+pub struct NetErrors {
+    pub max_eps: f32,
+}
+impl MetricSource for NetErrors {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (256.0 * (snap.net_errors as f32) / self.max_eps).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "net_errors"
+    }
+}
+
+#[derive(Debug)]
+pub struct DiskUtil;
+impl MetricSource for DiskUtil {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (snap.disk_util as f32 * 2.56).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "disk_util"
+    }
+}
+
+// combined read+write sectors/sec on the busiest selected disk, scaled by
+// sectors_max -- for users who'd rather have one disk-activity gauge than
+// split it across two channels
+#[derive(Debug)]
+pub struct DiskIo {
+    pub sectors_max: f32,
+}
+impl MetricSource for DiskIo {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (256.0 * snap.disk_io as f32 / self.sectors_max).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+}
+
+// sectors/sec on the busiest-reading selected disk, scaled by sectors_max
+#[derive(Debug)]
+pub struct DiskRead {
+    pub sectors_max: f32,
+}
+impl MetricSource for DiskRead {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (256.0 * snap.disk_read as f32 / self.sectors_max).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "disk_read"
+    }
+}
+
+// sectors/sec on the busiest-writing selected disk, scaled by sectors_max
+#[derive(Debug)]
+pub struct DiskWrite {
+    pub sectors_max: f32,
+}
+impl MetricSource for DiskWrite {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (256.0 * snap.disk_write as f32 / self.sectors_max).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "disk_write"
+    }
+}
+
+#[derive(Debug)]
+pub struct MemUsage;
+impl MetricSource for MemUsage {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        (snap.mem_pct * 2.56).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "mem"
+    }
+}
+
+// normalized against core count, so full scale is reached at load == n_cpu
+#[derive(Debug)]
+pub struct LoadAvg {
+    pub n_cpu: usize,
+}
+impl MetricSource for LoadAvg {
+    fn sample(&self, snap: &StatsSnapshot) -> f32 {
+        let n = (self.n_cpu.max(1)) as f32;
+        (256.0 * snap.loadavg / n).clamp(0.0, 255.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "loadavg"
+    }
+}
+
+// a channel's metric source plus its own PWM scaling. Unidirectional
+// sources (cpu, net_errors, disk_util, disk_read, disk_write, mem, loadavg,
+// and net with --net-gauge-abs) scale straight from pwm_min (gauge == 0) to
+// pwm_max (gauge == 255). Bidirectional sources (net throughput without
+// --net-gauge-abs) pivot on pwm_zero at gauge == 0, swinging down towards
+// pwm_min or up towards pwm_max depending on direction.
+pub struct ChannelMapping {
+    pub source: Box<dyn MetricSource>,
+    pub pwm_min: f32,
+    pub pwm_zero: f32,
+    pub pwm_max: f32,
+}
+
+impl ChannelMapping {
+    pub fn pwm(&self, snap: &StatsSnapshot) -> f32 {
+        let gauge = self.source.sample(snap).clamp(-255.0, 255.0);
+        if self.source.is_bidirectional() {
+            if gauge < 0.0 {
+                (self.pwm_zero + gauge * (self.pwm_zero - self.pwm_min) / 256.0).clamp(0.0, 255.0)
+            } else {
+                (self.pwm_zero + gauge * (self.pwm_max - self.pwm_zero) / 256.0).clamp(0.0, 255.0)
+            }
+        } else {
+            // unidirectional: pwm_min is the floor at gauge == 0, scaling
+            // straight up to pwm_max at gauge == 255
+            (self.pwm_min + gauge.max(0.0) * (self.pwm_max - self.pwm_min) / 256.0)
+                .clamp(0.0, 255.0)
+        }
+    }
+}
+
+fn parse_metric_source(name: &str, opts: &OptsCommon, n_cpu: usize) -> anyhow::Result<Box<dyn MetricSource>> {
+    Ok(match name {
+        "cpu" => Box::new(CpuUsage { n_cpu }),
+        "net" => Box::new(NetThroughput {
+            gauge_abs: opts.net_gauge_abs,
+            gauge_mbps: opts.net_gauge_mbps,
+        }),
+        "net_errors" => Box::new(NetErrors {
+            max_eps: opts.net_errors_max_eps,
+        }),
+        "disk_util" => Box::new(DiskUtil),
+        "disk" => Box::new(DiskIo {
+            sectors_max: opts.disk_sectors_max,
+        }),
+        "disk_read" => Box::new(DiskRead {
+            sectors_max: opts.disk_sectors_max,
+        }),
+        "disk_write" => Box::new(DiskWrite {
+            sectors_max: opts.disk_sectors_max,
+        }),
+        "mem" => Box::new(MemUsage),
+        "loadavg" => Box::new(LoadAvg { n_cpu }),
+        other => bail!("Unknown metric source {other:?} (expected one of: cpu, net, net_errors, disk_util, disk, disk_read, disk_write, mem, loadavg)"),
+    })
+}
+
+// build the four channel mappings (ch0..ch3) from --ch0/--ch1/--ch2/--ch3
+// and their matching --chN-pwm-{min,zero,max} options
+pub fn build_channel_map(opts: &OptsCommon, n_cpu: usize) -> anyhow::Result<Vec<ChannelMapping>> {
+    let specs = [&opts.ch0, &opts.ch1, &opts.ch2, &opts.ch3];
+    let bounds = [
+        (opts.ch0_pwm_min, opts.ch0_pwm_zero, opts.ch0_pwm_max),
+        (opts.ch1_pwm_min, opts.ch1_pwm_zero, opts.ch1_pwm_max),
+        (opts.ch2_pwm_min, opts.ch2_pwm_zero, opts.ch2_pwm_max),
+        (opts.ch3_pwm_min, opts.ch3_pwm_zero, opts.ch3_pwm_max),
+    ];
+
+    let mut channels = Vec::with_capacity(N_CHANS);
+    for (spec, (pwm_min, pwm_zero, pwm_max)) in specs.into_iter().zip(bounds) {
+        channels.push(ChannelMapping {
+            source: parse_metric_source(spec, opts, n_cpu)?,
+            pwm_min,
+            pwm_zero,
+            pwm_max,
+        });
+    }
+    Ok(channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap() -> StatsSnapshot {
+        StatsSnapshot {
+            cpu_rates: vec![80.0, 60.0],
+            net_bits: 0,
+            net_errors: 5.0,
+            disk_io: 1000.0,
+            disk_read: 800.0,
+            disk_write: 200.0,
+            disk_util: 40.0,
+            mem_pct: 50.0,
+            loadavg: 2.0,
+        }
+    }
+
+    fn test_opts() -> OptsCommon {
+        OptsCommon {
+            ch0: "cpu".into(),
+            ch1: "net".into(),
+            ch2: "disk_util".into(),
+            ch3: "mem".into(),
+            ch0_pwm_min: 0.0,
+            ch0_pwm_zero: 0.0,
+            ch0_pwm_max: 255.0,
+            ch1_pwm_min: 0.0,
+            ch1_pwm_zero: 128.0,
+            ch1_pwm_max: 255.0,
+            ch2_pwm_min: 0.0,
+            ch2_pwm_zero: 0.0,
+            ch2_pwm_max: 255.0,
+            ch3_pwm_min: 50.0,
+            ch3_pwm_zero: 0.0,
+            ch3_pwm_max: 255.0,
+            net_gauge_abs: false,
+            net_gauge_mbps: 100.0,
+            net_errors_max_eps: 10.0,
+            disk_sectors_max: 1000.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cpu_usage_averages_top_cores_then_scales_for_low_core_counts() {
+        let source = CpuUsage { n_cpu: 2 };
+        // n_cpu < 4 falls into the "scale by 2.56" branch
+        assert!((source.sample(&snap()) - 179.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn disk_read_and_write_map_to_distinct_gauges() {
+        let s = snap();
+        let read = DiskRead { sectors_max: 1000.0 }.sample(&s);
+        let write = DiskWrite { sectors_max: 1000.0 }.sample(&s);
+        assert!((read - 204.8).abs() < 0.01);
+        assert!((write - 51.2).abs() < 0.01);
+        assert!(read > write);
+    }
+
+    #[test]
+    fn disk_io_combines_read_and_write_into_one_gauge() {
+        // disk_io (1000.0) / sectors_max (1000.0) * 256 saturates at 255
+        let combined = DiskIo { sectors_max: 1000.0 }.sample(&snap());
+        assert!((combined - 255.0).abs() < 0.01);
+
+        let combined = DiskIo { sectors_max: 4000.0 }.sample(&snap());
+        assert!((combined - 64.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn net_throughput_pivots_on_pwm_zero_when_bidirectional() {
+        let source = NetThroughput {
+            gauge_abs: false,
+            gauge_mbps: 100.0,
+        };
+        assert!(source.is_bidirectional());
+        let mapping = ChannelMapping {
+            source: Box::new(source),
+            pwm_min: 0.0,
+            pwm_zero: 128.0,
+            pwm_max: 255.0,
+        };
+        assert_eq!(mapping.pwm(&snap()), 128.0);
+    }
+
+    #[test]
+    fn unidirectional_source_uses_pwm_min_as_the_floor() {
+        let mapping = ChannelMapping {
+            source: Box::new(MemUsage),
+            pwm_min: 50.0,
+            pwm_zero: 0.0,
+            pwm_max: 255.0,
+        };
+        let mut s = snap();
+        s.mem_pct = 0.0;
+        assert_eq!(mapping.pwm(&s), 50.0);
+    }
+
+    #[test]
+    fn build_channel_map_rejects_unknown_source() {
+        let mut opts = test_opts();
+        opts.ch0 = "bogus".into();
+        assert!(build_channel_map(&opts, 4).is_err());
+    }
+
+    // mirrors exactly what src/bin/perf_meters.rs's measure loop does with
+    // the channel map, the shared Vu and the transport it was given
+    #[test]
+    fn full_pipeline_feeds_synthetic_snapshot_through_vu_into_recorded_frames() {
+        let opts = test_opts();
+        let channels = build_channel_map(&opts, 4).unwrap();
+        assert_eq!(channels.len(), N_CHANS);
+
+        let mut vu = Vu::new(60, 300);
+        let mut transport = RecordingTransport::default();
+        let s = snap();
+        let dt = std::time::Duration::from_millis(300);
+
+        for (i, ch) in channels.iter().enumerate() {
+            let pwm = ch.pwm(&s) as i16;
+            vu.set(&mut transport, Channel::from_index(i as u8), pwm, dt).unwrap();
+        }
+
+        assert_eq!(transport.frames.len(), N_CHANS);
+        for (i, frame) in transport.frames.iter().enumerate() {
+            assert_eq!(frame[0], 0xFD);
+            assert_eq!(frame[1], 0x02);
+            assert_eq!(frame[2], 0x30 + i as u8);
+        }
+        // dt is 5 attack time-constants, so the gauge should have all but
+        // reached its target pwm for every channel
+        for (ch, frame) in channels.iter().zip(transport.frames.iter()) {
+            let target = ch.pwm(&s).round() as i16;
+            assert!((frame[3] as i16 - target).abs() <= 2);
+        }
+    }
+}
+// EOF
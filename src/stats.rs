@@ -7,114 +7,342 @@ use sysinfo::*;
 use crate::*;
 
 const DISK_STATS: &str = "/proc/diskstats";
+const NET_DEV: &str = "/proc/net/dev";
+const NET_SNMP: &str = "/proc/net/snmp";
+const LOADAVG: &str = "/proc/loadavg";
 
+fn iface_selected(name: &str, include: &[String], exclude: &[String]) -> bool {
+    if exclude.iter().any(|e| e == name) {
+        return false;
+    }
+    include.is_empty() || include.iter().any(|i| i == name)
+}
+
+// parse one "name: rest-of-fields" line from /proc/net/dev, returning the
+// summed rx+tx errors and drops if the interface is selected
+fn parse_iface_errors(name: &str, rest: &str, include: &[String], exclude: &[String]) -> Option<i64> {
+    let name = name.trim();
+    if !iface_selected(name, include, exclude) {
+        return None;
+    }
+    let items = rest.split_ascii_whitespace().collect::<Vec<&str>>();
+    if items.len() < 16 {
+        return None;
+    }
+    let rx_errs = items[2].parse::<i64>().unwrap_or(0);
+    let rx_drop = items[3].parse::<i64>().unwrap_or(0);
+    let tx_errs = items[10].parse::<i64>().unwrap_or(0);
+    let tx_drop = items[11].parse::<i64>().unwrap_or(0);
+    Some(rx_errs + rx_drop + tx_errs + tx_drop)
+}
+
+// sum InErrors, RcvbufErrors, SndbufErrors and NoPorts given the "Udp:"
+// header and values lines from /proc/net/snmp (prefix already stripped)
+fn parse_udp_errors(header: &str, values: &str) -> i64 {
+    const WANTED: [&str; 4] = ["InErrors", "RcvbufErrors", "SndbufErrors", "NoPorts"];
+
+    let header = header.split_ascii_whitespace().collect::<Vec<&str>>();
+    let values = values.split_ascii_whitespace().collect::<Vec<&str>>();
+
+    let mut total = 0i64;
+    for field in WANTED {
+        if let Some(pos) = header.iter().position(|h| *h == field) {
+            total += values.get(pos).and_then(|v| v.parse::<i64>().ok()).unwrap_or(0);
+        }
+    }
+    total
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct DiskSample {
+    sect_rd: i64,
+    sect_wrt: i64,
+    io_ms: i64,
+}
+
+// true for whole-disk devices (sda, nvme0n1, mmcblk0), false for their
+// partitions (sda1, nvme0n1p1, mmcblk0p1)
+fn is_whole_disk(name: &str) -> bool {
+    for prefix in ["sd", "vd", "hd"] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return !rest.is_empty() && rest.chars().all(|c| c.is_ascii_lowercase());
+        }
+    }
+    if name.starts_with("nvme") || name.starts_with("mmcblk") {
+        // partitions carry a trailing "pN", whole disks don't
+        return !name.contains('p');
+    }
+    false
+}
 
 #[derive(Debug)]
 pub struct DiskStats {
     prev_ts: time::Instant,
-    prev_stats: HashMap<String, (i64, i64)>,
-    rates: Vec<f64>,
+    prev_stats: HashMap<String, DiskSample>,
+    device_filter: Vec<String>,
+    io_rates: Vec<f64>,
+    read_rates: Vec<f64>,
+    write_rates: Vec<f64>,
+    util: Vec<f64>,
 }
 
 impl DiskStats {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(device_filter: Vec<String>) -> anyhow::Result<Self> {
         Ok(Self {
             prev_ts: time::Instant::now(),
-            prev_stats: Self::read_diskstats()?,
-            rates: Vec::new(),
+            prev_stats: Self::read_diskstats(&device_filter)?,
+            device_filter,
+            io_rates: Vec::new(),
+            read_rates: Vec::new(),
+            write_rates: Vec::new(),
+            util: Vec::new(),
         })
     }
 
     pub fn refresh(&mut self) -> anyhow::Result<()> {
-        self.rates = self.diskrates()?;
-        Ok(())
-    }
-
-    pub fn rates(&self) -> &Vec<f64> {
-        &self.rates
-    }
-
-    fn diskrates(&mut self) -> anyhow::Result<Vec<f64>> {
-        let us = self.prev_ts.elapsed().as_micros();
+        let us = self.prev_ts.elapsed().as_micros().max(1) as f64;
+        let ms = us / 1000.0;
         self.prev_ts = time::Instant::now();
 
-        let stats = Self::read_diskstats()?;
-        let mut rates = Vec::with_capacity(stats.len());
+        let stats = Self::read_diskstats(&self.device_filter)?;
+        let mut io_rates = Vec::with_capacity(stats.len());
+        let mut read_rates = Vec::with_capacity(stats.len());
+        let mut write_rates = Vec::with_capacity(stats.len());
+        let mut util = Vec::with_capacity(stats.len());
 
-        for (k, v) in &stats {
-            match self.prev_stats.get(k) {
-                None => continue,
-                Some(prev) => {
-                    let sect_rd = v.0 - prev.0;
-                    let sect_wrt = v.1 - prev.1;
-                    rates.push((sect_rd + sect_wrt) as f64 * 1_000_000.0 / us as f64);
-                }
+        for (name, cur) in &stats {
+            if let Some(prev) = self.prev_stats.get(name) {
+                let rd = (cur.sect_rd - prev.sect_rd) as f64 * 1_000_000.0 / us;
+                let wrt = (cur.sect_wrt - prev.sect_wrt) as f64 * 1_000_000.0 / us;
+                io_rates.push(rd + wrt);
+                read_rates.push(rd);
+                write_rates.push(wrt);
+                let busy_ms = (cur.io_ms - prev.io_ms) as f64;
+                util.push((busy_ms / ms * 100.0).clamp(0.0, 100.0));
             }
         }
         // Rust refuses to just sort() f64, because NaN, Inf etc.
-        rates.sort_by(|a, b| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        let by_desc = |a: &f64, b: &f64| b.partial_cmp(a).unwrap_or(Ordering::Equal);
+        io_rates.sort_by(by_desc);
+        read_rates.sort_by(by_desc);
+        write_rates.sort_by(by_desc);
+        util.sort_by(by_desc);
+
+        self.io_rates = io_rates;
+        self.read_rates = read_rates;
+        self.write_rates = write_rates;
+        self.util = util;
         self.prev_stats = stats;
-        Ok(rates)
+        Ok(())
+    }
+
+    // per-device combined read+write rate, largest first
+    pub fn rates(&self) -> Vec<f64> {
+        self.io_rates.clone()
+    }
+
+    pub fn read_rates(&self) -> &Vec<f64> {
+        &self.read_rates
+    }
+
+    pub fn write_rates(&self) -> &Vec<f64> {
+        &self.write_rates
+    }
+
+    // per-device utilization percentage (0..100), largest first
+    pub fn util(&self) -> &Vec<f64> {
+        &self.util
     }
 
     // https://www.kernel.org/doc/Documentation/ABI/testing/procfs-diskstats
-    fn read_diskstats() -> anyhow::Result<HashMap<String, (i64, i64)>> {
+    fn read_diskstats(device_filter: &[String]) -> anyhow::Result<HashMap<String, DiskSample>> {
         let mut stats = HashMap::with_capacity(32);
         for line in io::BufReader::new(File::open(DISK_STATS)?).lines() {
             let line = line?;
             let items = line.split_ascii_whitespace().collect::<Vec<&str>>();
             let devname = items[2];
-            // collect sectors read and sectors written from "sd?" and "nvme???"
-            if devname.starts_with("sd") && devname.len() == 3
-                || devname.starts_with("nvme") && devname.len() == 7
-            {
-                let sect_rd = items[5].parse::<i64>()?;
-                let sect_wrt = items[9].parse::<i64>()?;
-                stats.insert(devname.into(), (sect_rd, sect_wrt));
+
+            let selected = if device_filter.is_empty() {
+                is_whole_disk(devname)
+            } else {
+                device_filter.iter().any(|d| d == devname)
+            };
+            if !selected {
+                continue;
             }
+
+            let sect_rd = items[5].parse::<i64>()?;
+            let sect_wrt = items[9].parse::<i64>()?;
+            // field 10 (item 12, counting major, minor and name as items
+            // 0..2) -- time spent doing I/Os, in ms;
+            // this is what makes a proper "disk busy" utilization signal
+            let io_ms = items.get(12).and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+            stats.insert(
+                devname.into(),
+                DiskSample {
+                    sect_rd,
+                    sect_wrt,
+                    io_ms,
+                },
+            );
         }
         Ok(stats)
     }
 }
 
 
+// tracks the rate of network errors/drops (per selected interface, from
+// /proc/net/dev) plus UDP buffer errors (from /proc/net/snmp) as a single
+// combined counter, the same delta-over-time approach as DiskStats
+#[derive(Debug)]
+pub struct NetErrStats {
+    prev_ts: time::Instant,
+    prev_count: i64,
+    rate: f64,
+    iface_include: Vec<String>,
+    iface_exclude: Vec<String>,
+}
+
+impl NetErrStats {
+    pub fn new(iface_include: Vec<String>, iface_exclude: Vec<String>) -> anyhow::Result<Self> {
+        let prev_count = Self::read_counts(&iface_include, &iface_exclude)?;
+        Ok(Self {
+            prev_ts: time::Instant::now(),
+            prev_count,
+            rate: 0.0,
+            iface_include,
+            iface_exclude,
+        })
+    }
+
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        let us = self.prev_ts.elapsed().as_micros().max(1) as f64;
+        self.prev_ts = time::Instant::now();
+
+        let count = Self::read_counts(&self.iface_include, &self.iface_exclude)?;
+        let delta = (count - self.prev_count).max(0);
+        self.rate = delta as f64 * 1_000_000.0 / us;
+        self.prev_count = count;
+        Ok(())
+    }
+
+    // errors+drops per second, interfaces and UDP buffer errors combined
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    fn read_counts(include: &[String], exclude: &[String]) -> anyhow::Result<i64> {
+        Ok(Self::read_iface_errors(include, exclude)? + Self::read_udp_errors()?)
+    }
+
+    // sum rx+tx errors and drops across selected interfaces
+    fn read_iface_errors(include: &[String], exclude: &[String]) -> anyhow::Result<i64> {
+        let mut total = 0i64;
+        for line in io::BufReader::new(File::open(NET_DEV)?).lines().skip(2) {
+            let line = line?;
+            let Some((name, rest)) = line.split_once(':') else {
+                continue;
+            };
+            total += parse_iface_errors(name, rest, include, exclude).unwrap_or(0);
+        }
+        Ok(total)
+    }
+
+    // sum InErrors, RcvbufErrors, SndbufErrors and NoPorts from the "Udp:"
+    // section of /proc/net/snmp
+    fn read_udp_errors() -> anyhow::Result<i64> {
+        let mut lines = io::BufReader::new(File::open(NET_SNMP)?).lines();
+        while let Some(line) = lines.next() {
+            let line = line?;
+            let Some(header) = line.strip_prefix("Udp:") else {
+                continue;
+            };
+            let Some(values) = lines.next() else {
+                break;
+            };
+            let values = values?;
+            let Some(values) = values.strip_prefix("Udp:") else {
+                break;
+            };
+            return Ok(parse_udp_errors(header, values));
+        }
+        Ok(0)
+    }
+}
+
+
 #[derive(Debug)]
 pub struct MyStats {
     sys: System,
-    refresh: RefreshKind,
+    cpu_refresh: RefreshKind,
+    mem_refresh: RefreshKind,
     networks: Networks,
     n_cpu: usize,
     diskstats: DiskStats,
+    net_err: NetErrStats,
+    net_iface_include: Vec<String>,
+    net_iface_exclude: Vec<String>,
 }
 
 impl MyStats {
-    pub fn new() -> Self {
+    pub fn new(opts: &OptsCommon) -> Self {
         let mut sys = System::new_all();
         sys.refresh_all();
-        let refresh = RefreshKind::new()
-            .with_cpu(CpuRefreshKind::new().with_cpu_usage())
-            .with_memory(MemoryRefreshKind::everything().without_swap());
+        let cpu_refresh = RefreshKind::new().with_cpu(CpuRefreshKind::new().with_cpu_usage());
+        let mem_refresh =
+            RefreshKind::new().with_memory(MemoryRefreshKind::everything().without_swap());
         let networks = Networks::new_with_refreshed_list();
         let n_cpu = sys.physical_core_count().unwrap_or(1);
-        let diskstats = DiskStats::new().expect("Unable to get disk statistics");
+        let diskstats =
+            DiskStats::new(opts.disk_device.clone()).expect("Unable to get disk statistics");
+        let net_iface_include = opts.net_iface_include.clone();
+        let net_iface_exclude = opts.net_iface_exclude.clone();
+        let net_err = NetErrStats::new(net_iface_include.clone(), net_iface_exclude.clone())
+            .expect("Unable to get network error statistics");
 
         MyStats {
             sys,
-            refresh,
+            cpu_refresh,
+            mem_refresh,
             networks,
             n_cpu,
             diskstats,
+            net_err,
+            net_iface_include,
+            net_iface_exclude,
         }
     }
 
+    // refresh everything at once -- kept for callers that don't care about
+    // per-metric cadence; MonitorService drives the granular methods below
     pub fn refresh(&mut self) {
-        self.sys.refresh_specifics(self.refresh);
-        self.networks.refresh();
-        if let Err(e) = self.diskstats.refresh() {
+        self.refresh_cpu();
+        self.refresh_mem();
+        self.refresh_net();
+        if let Err(e) = self.refresh_disk() {
             error!("Error refreshing diskstats: {e} (ignored)");
         }
     }
 
+    pub fn refresh_cpu(&mut self) {
+        self.sys.refresh_specifics(self.cpu_refresh);
+    }
+
+    pub fn refresh_mem(&mut self) {
+        self.sys.refresh_specifics(self.mem_refresh);
+    }
+
+    pub fn refresh_net(&mut self) {
+        self.networks.refresh();
+        if let Err(e) = self.net_err.refresh() {
+            error!("Error refreshing network error stats: {e} (ignored)");
+        }
+    }
+
+    pub fn refresh_disk(&mut self) -> anyhow::Result<()> {
+        self.diskstats.refresh()
+    }
+
     pub fn sys(&self) -> &System {
         &self.sys
     }
@@ -155,25 +383,45 @@ impl MyStats {
         usages
     }
 
-    // return number of bits transferred
+    // return number of bits transferred, across selected interfaces
     pub fn net_bits(&self) -> i64 {
         let mut rx: i64 = 0;
         let mut tx: i64 = 0;
 
-        for (_iface, data) in self.networks.iter() {
+        for (iface, data) in self.networks.iter() {
+            if !iface_selected(iface, &self.net_iface_include, &self.net_iface_exclude) {
+                continue;
+            }
             rx = rx.saturating_add(i64::try_from(data.received()).unwrap_or(0));
             tx = tx.saturating_add(i64::try_from(data.transmitted()).unwrap_or(0));
         }
         rx.saturating_add(tx).saturating_mul(8)
     }
 
+    // return the rate (per second) of interface errors/drops plus UDP
+    // buffer errors, across selected interfaces
+    pub fn net_errors(&self) -> f64 {
+        self.net_err.rate()
+    }
+
     // return sectors read+written on the most active disk
     pub fn disk_io(&self) -> f64 {
-        match self.diskstats.rates.first()
-        {
-            None => 0.0,
-            Some(r) => *r
-        }
+        self.diskstats.rates().first().copied().unwrap_or(0.0)
+    }
+
+    // return sectors read on the busiest-reading selected disk
+    pub fn disk_read_io(&self) -> f64 {
+        self.diskstats.read_rates().first().copied().unwrap_or(0.0)
+    }
+
+    // return sectors written on the busiest-writing selected disk
+    pub fn disk_write_io(&self) -> f64 {
+        self.diskstats.write_rates().first().copied().unwrap_or(0.0)
+    }
+
+    // return the max utilization percentage (0..100) across selected disks
+    pub fn disk_util(&self) -> f64 {
+        self.diskstats.util().first().copied().unwrap_or(0.0)
     }
 
 
@@ -183,11 +431,92 @@ impl MyStats {
         let total = f64::value_from(self.sys.total_memory()).unwrap_or(0.0);
         100.0 * ((used / total) as f32)
     }
+
+    // return the 1-minute load average from /proc/loadavg
+    pub fn load_avg(&self) -> f32 {
+        std::fs::read_to_string(LOADAVG)
+            .ok()
+            .and_then(|s| s.split_ascii_whitespace().next().map(str::to_string))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.0)
+    }
 }
 
 impl Default for MyStats {
     fn default() -> Self {
-        Self::new()
+        Self::new(&OptsCommon::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_whole_disk_accepts_whole_disks_and_rejects_partitions() {
+        let cases = [
+            ("sda", true),
+            ("sda1", false),
+            ("vdb", true),
+            ("vdb2", false),
+            ("hdc", true),
+            ("hdc3", false),
+            ("nvme0n1", true),
+            ("nvme0n1p1", false),
+            ("mmcblk0", true),
+            ("mmcblk0p1", false),
+            ("loop0", false),
+            ("dm-0", false),
+            ("", false),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(is_whole_disk(name), expected, "is_whole_disk({name:?})");
+        }
+    }
+
+    #[test]
+    fn parse_iface_errors_sums_rx_tx_errors_and_drops() {
+        let include = [];
+        let exclude = [String::from("lo")];
+        // fields (0-indexed): 2=rx_errs 3=rx_drop ... 10=tx_errs 11=tx_drop
+        let rest = "1234 10   2   3 0    0    0     0        5678  20  30  40 0    0    0     0";
+        assert_eq!(
+            parse_iface_errors("eth0", rest, &include, &exclude),
+            Some(2 + 3 + 30 + 40)
+        );
+    }
+
+    #[test]
+    fn parse_iface_errors_respects_include_exclude_filters() {
+        let rest = "1234 10   2   3 0    0    0     0        5678  20  30  40 0    0    0     0";
+        assert_eq!(parse_iface_errors("lo", rest, &[], &[String::from("lo")]), None);
+        assert_eq!(
+            parse_iface_errors("eth0", rest, &[String::from("eth1")], &[]),
+            None
+        );
+        assert_eq!(
+            parse_iface_errors("eth0", rest, &[String::from("eth0")], &[]).is_some(),
+            true
+        );
+    }
+
+    #[test]
+    fn parse_iface_errors_rejects_short_lines() {
+        assert_eq!(parse_iface_errors("eth0", "1234 10 2 3", &[], &[]), None);
+    }
+
+    #[test]
+    fn parse_udp_errors_sums_wanted_fields_in_any_order() {
+        let header = "Udp: InDatagrams NoPorts InErrors OutDatagrams RcvbufErrors SndbufErrors";
+        let values = "Udp: 100          4       5        200          6            7";
+        assert_eq!(parse_udp_errors(header.trim_start_matches("Udp:"), values.trim_start_matches("Udp:")), 4 + 5 + 6 + 7);
+    }
+
+    #[test]
+    fn parse_udp_errors_ignores_missing_fields() {
+        let header = "InDatagrams OutDatagrams";
+        let values = "100 200";
+        assert_eq!(parse_udp_errors(header, values), 0);
     }
 }
 
@@ -0,0 +1,50 @@
+// transport.rs
+
+use crate::*;
+
+// Carries one 4-byte gauge frame [0xFD, 0x02, 0x30+channel, value] to
+// wherever it needs to go. Abstracting this out of Vu/hello/calibrate lets
+// the whole pipeline -- ballistics, routing, protocol framing -- run and be
+// asserted on without a device attached.
+pub trait VuTransport {
+    fn send_frame(&mut self, channel: u8, value: u8) -> anyhow::Result<()>;
+}
+
+impl VuTransport for Box<dyn SerialPort> {
+    fn send_frame(&mut self, channel: u8, value: u8) -> anyhow::Result<()> {
+        let cmd_buf: [u8; 4] = [0xFD, 0x02, 0x30 + channel, value];
+        Ok(self.write_all(&cmd_buf)?)
+    }
+}
+
+// discards every frame
+#[derive(Debug, Default)]
+pub struct NullTransport;
+impl VuTransport for NullTransport {
+    fn send_frame(&mut self, _channel: u8, _value: u8) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+// captures every frame for later assertions
+#[derive(Debug, Default)]
+pub struct RecordingTransport {
+    pub frames: Vec<[u8; 4]>,
+}
+impl VuTransport for RecordingTransport {
+    fn send_frame(&mut self, channel: u8, value: u8) -> anyhow::Result<()> {
+        self.frames.push([0xFD, 0x02, 0x30 + channel, value]);
+        Ok(())
+    }
+}
+
+// prints each frame in hex, so the tool is observable with no device attached
+#[derive(Debug, Default)]
+pub struct StdoutTransport;
+impl VuTransport for StdoutTransport {
+    fn send_frame(&mut self, channel: u8, value: u8) -> anyhow::Result<()> {
+        println!("VU frame: fd 02 {:02x} {value:02x}", 0x30 + channel);
+        Ok(())
+    }
+}
+// EOF